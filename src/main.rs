@@ -1,4 +1,6 @@
 mod opencbm;
+mod raster;
+mod simulate;
 
 use svg2gcode::{
     Machine,
@@ -21,7 +23,7 @@ use clap::Parser;
 
 const PLOTTER_DEVICE: u8 = 6;
 const PLOTTER_SA_XY: u8 = 1;
-const PLOTTER_SA_RESET: u8 = 7;
+const PLOTTER_SA_SELECT: u8 = 2;
 
 #[derive(Default)]
 struct MoveOptions {
@@ -70,6 +72,365 @@ impl Commands {
     }
 }
 
+/// A single point visited during plotting, plotter units (not mm).
+type Point = (u32, u32);
+
+/// One command in the fully-resolved plotting plan: a pen-up reposition, a
+/// pen-down line, or a pen carousel change.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub(crate) enum PlotCommand {
+    Move(u32, u32),
+    Draw(u32, u32),
+    SelectPen(u8),
+}
+
+impl PlotCommand {
+    fn point(&self) -> Option<Point> {
+        match *self {
+            Self::Move(x, y) | Self::Draw(x, y) => Some((x, y)),
+            Self::SelectPen(_) => None,
+        }
+    }
+}
+
+/// A contiguous run of pen-down points, bounded by the pen-up move that preceded it.
+///
+/// `points[0]` is the pen-up target (where the stroke starts) and the remaining
+/// entries are the `Draw` points in document order. A stroke may be emitted in
+/// reverse, but the points within it are never otherwise reordered.
+struct Stroke {
+    points: Vec<Point>,
+}
+
+impl Stroke {
+    fn start(&self) -> Point {
+        self.points[0]
+    }
+
+    fn end(&self) -> Point {
+        *self.points.last().unwrap()
+    }
+}
+
+/// Rasterizes pen-down segments of a plan into a `width`x`height` monochrome grid,
+/// scaled to fit the plan's bounding box and flipped so the plotter's bottom-left
+/// origin lands at the bottom of the image.
+fn rasterize_plan(plan: &[PlotCommand], width: u32, height: u32) -> Vec<Vec<bool>> {
+    let mut grid = vec![vec![false; width as usize]; height as usize];
+
+    if plan.is_empty() || width == 0 || height == 0 {
+        return grid;
+    }
+
+    let (min_x, max_x, min_y, max_y) = plan.iter().filter_map(PlotCommand::point).fold(
+        (u32::MAX, 0u32, u32::MAX, 0u32),
+        |(min_x, max_x, min_y, max_y), (x, y)| {
+            (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+        },
+    );
+
+    let span_x = (max_x - min_x).max(1) as f64;
+    let span_y = (max_y - min_y).max(1) as f64;
+
+    let to_pixel = |x: u32, y: u32| -> (i64, i64) {
+        let px = (x - min_x) as f64 / span_x * (width - 1) as f64;
+        let py = (y - min_y) as f64 / span_y * (height - 1) as f64;
+        (px.round() as i64, (height - 1) as i64 - py.round() as i64)
+    };
+
+    let mut set_pixel = |px: i64, py: i64| {
+        if px >= 0 && py >= 0 && (px as u32) < width && (py as u32) < height {
+            grid[py as usize][px as usize] = true;
+        }
+    };
+
+    let mut prev: Option<Point> = None;
+
+    for command in plan {
+        match *command {
+            PlotCommand::Move(x, y) => prev = Some((x, y)),
+            PlotCommand::Draw(x, y) => {
+                if let Some(prev_point) = prev {
+                    let (x0, y0) = to_pixel(prev_point.0, prev_point.1);
+                    let (x1, y1) = to_pixel(x, y);
+                    bresenham(x0, y0, x1, y1, &mut set_pixel);
+                }
+                prev = Some((x, y));
+            }
+            PlotCommand::SelectPen(_) => {}
+        }
+    }
+
+    grid
+}
+
+/// Rasterizes a line between two points using Bresenham's algorithm, calling
+/// `plot` once per pixel (including both endpoints).
+fn bresenham(x0: i64, y0: i64, x1: i64, y1: i64, plot: &mut impl FnMut(i64, i64)) {
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+
+    loop {
+        plot(x, y);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Prints a monochrome bitmap to the terminal as a sixel image (DCS sixel sequence).
+fn print_sixel_preview(grid: &[Vec<bool>]) {
+    let height = grid.len();
+    let width = grid.first().map_or(0, Vec::len);
+
+    print!("\x1bPq");
+    print!("#0;2;100;100;100");
+
+    let mut y = 0;
+    while y < height {
+        print!("#0");
+        for x in 0..width {
+            let mut mask = 0u8;
+            for bit in 0..6 {
+                if grid.get(y + bit).is_some_and(|row| row[x]) {
+                    mask |= 1 << bit;
+                }
+            }
+            print!("{}", (0x3Fu8 + mask) as char);
+        }
+        print!("-");
+        y += 6;
+    }
+
+    print!("\x1b\\");
+    println!();
+}
+
+fn pen_up_distance(a: Point, b: Point) -> f64 {
+    let dx = a.0 as f64 - b.0 as f64;
+    let dy = a.1 as f64 - b.1 as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Splits a flat plan into strokes, one per contiguous `Draw` run.
+fn build_strokes(plan: &[PlotCommand]) -> Vec<Stroke> {
+    let mut strokes = Vec::new();
+    let mut current: Vec<Point> = Vec::new();
+
+    for command in plan {
+        match *command {
+            PlotCommand::Move(x, y) => {
+                if current.len() > 1 {
+                    strokes.push(Stroke { points: current });
+                }
+                current = vec![(x, y)];
+            }
+            PlotCommand::Draw(x, y) => {
+                current.push((x, y));
+            }
+            PlotCommand::SelectPen(_) => {}
+        }
+    }
+
+    if current.len() > 1 {
+        strokes.push(Stroke { points: current });
+    }
+
+    strokes
+}
+
+/// Greedy nearest-neighbor ordering: starting from `start` (wherever the pen
+/// actually is), repeatedly jump to whichever unvisited stroke endpoint (start
+/// or end) is closest, reversing the stroke in place if its far end was the
+/// one chosen.
+fn nearest_neighbor_order(mut remaining: Vec<Stroke>, start: Point) -> Vec<Stroke> {
+    let mut ordered = Vec::with_capacity(remaining.len());
+    let mut pos: Point = start;
+
+    while !remaining.is_empty() {
+        let (idx, reverse) = remaining
+            .iter()
+            .enumerate()
+            .map(|(i, stroke)| {
+                let to_start = pen_up_distance(pos, stroke.start());
+                let to_end = pen_up_distance(pos, stroke.end());
+                if to_start <= to_end {
+                    (i, false, to_start)
+                } else {
+                    (i, true, to_end)
+                }
+            })
+            .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
+            .map(|(i, reverse, _)| (i, reverse))
+            .expect("remaining is non-empty");
+
+        let mut stroke = remaining.remove(idx);
+        if reverse {
+            stroke.points.reverse();
+        }
+        pos = stroke.end();
+        ordered.push(stroke);
+    }
+
+    ordered
+}
+
+/// Refines a stroke order with path 2-opt: repeatedly reverse a sub-range of the
+/// tour (and the orientation of every stroke inside it) whenever doing so shortens
+/// the two pen-up edges that bound the range. `start` is wherever the pen
+/// actually is when this tour begins.
+fn two_opt_refine(strokes: &mut [Stroke], start: Point) {
+    let entry = |strokes: &[Stroke], i: usize| if i == 0 { start } else { strokes[i - 1].end() };
+
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..strokes.len().saturating_sub(1) {
+            for j in (i + 1)..strokes.len() {
+                let before = entry(strokes, i);
+                let after = strokes.get(j + 1).map(Stroke::start);
+
+                let current_cost = pen_up_distance(before, strokes[i].start())
+                    + after.map_or(0.0, |p| pen_up_distance(strokes[j].end(), p));
+                // After reversing strokes[i..=j] (order and each stroke's own
+                // points), the stroke now at position i is the old strokes[j]
+                // traversed backwards, so the tour enters it at strokes[j].end();
+                // symmetrically the tour leaves the old strokes[i] at its start().
+                let swapped_cost = pen_up_distance(before, strokes[j].end())
+                    + after.map_or(0.0, |p| pen_up_distance(strokes[i].start(), p));
+
+                if swapped_cost < current_cost {
+                    strokes[i..=j].reverse();
+                    for stroke in &mut strokes[i..=j] {
+                        stroke.points.reverse();
+                    }
+                    improved = true;
+                }
+            }
+        }
+    }
+}
+
+/// Reorders strokes to minimize total pen-up travel from `start` (wherever the
+/// pen actually is), leaving pen-down point order within each stroke untouched
+/// except for whole-stroke reversal.
+fn optimize_strokes(strokes: Vec<Stroke>, start: Point) -> Vec<Stroke> {
+    let mut ordered = nearest_neighbor_order(strokes, start);
+    two_opt_refine(&mut ordered, start);
+    ordered
+}
+
+/// Flattens an ordered list of strokes back into `Move`/`Draw` commands.
+fn strokes_to_plan(strokes: &[Stroke]) -> Vec<PlotCommand> {
+    let mut plan = Vec::new();
+    for stroke in strokes {
+        let (x, y) = stroke.start();
+        plan.push(PlotCommand::Move(x, y));
+        for &(x, y) in &stroke.points[1..] {
+            plan.push(PlotCommand::Draw(x, y));
+        }
+    }
+    plan
+}
+
+/// An RGB color, 0-255 per channel.
+type Color = [u8; 3];
+
+/// Resolves a CSS hex color (`#rgb` or `#rrggbb`) to RGB, ignoring anything else
+/// (named colors, `none`, `url(...)`, etc.) since the carousel only has discrete pens.
+fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.trim().strip_prefix('#')?;
+
+    let expand = |c: char| u8::from_str_radix(&c.to_string().repeat(2), 16).ok();
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+
+    match hex.len() {
+        6 => Some([channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?]),
+        3 => {
+            let mut chars = hex.chars();
+            Some([expand(chars.next()?)?, expand(chars.next()?)?, expand(chars.next()?)?])
+        }
+        _ => None,
+    }
+}
+
+/// Counts the subpaths in an SVG path `d` attribute: one for the implicit
+/// first moveto, plus one per subsequent `M`/`m` command (each lifts the pen
+/// and starts a new contiguous stroke).
+fn count_subpaths(d: &str) -> usize {
+    d.chars().filter(|&c| c == 'M' || c == 'm').count().max(1)
+}
+
+/// Parses `--pen` entries of the form `#rrggbb=index` into color-to-pen mappings,
+/// silently skipping malformed entries.
+fn parse_pen_mappings(entries: &[String]) -> Vec<(Color, u8)> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let (color, index) = entry.split_once('=')?;
+            Some((parse_color(color)?, index.trim().parse::<u8>().ok()?))
+        })
+        .collect()
+}
+
+fn color_distance(a: Color, b: Color) -> u32 {
+    a.iter().zip(b.iter()).map(|(&x, &y)| (x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+/// Quantizes a stroke color to the nearest configured pen, defaulting to pen 0
+/// when no `--pen` mappings were given.
+fn nearest_pen(color: Color, mappings: &[(Color, u8)]) -> u8 {
+    mappings
+        .iter()
+        .min_by_key(|(mapped_color, _)| color_distance(color, *mapped_color))
+        .map_or(0, |&(_, pen)| pen)
+}
+
+/// Groups strokes by pen index, preserving each pen's first-appearance order and
+/// each stroke's relative order within its pen, so the carriage switches pens as
+/// few times as possible.
+fn group_by_pen(strokes: Vec<Stroke>, pens: Vec<u8>) -> Vec<(u8, Vec<Stroke>)> {
+    let mut groups: Vec<(u8, Vec<Stroke>)> = Vec::new();
+
+    for (stroke, pen) in strokes.into_iter().zip(pens) {
+        match groups.iter_mut().find(|(existing_pen, _)| *existing_pen == pen) {
+            Some((_, group)) => group.push(stroke),
+            None => groups.push((pen, vec![stroke])),
+        }
+    }
+
+    groups
+}
+
+/// Verifies `stroke_colors` (one entry per subpath counted by `count_subpaths`)
+/// lines up 1:1 with `strokes` (one entry per subpath `build_strokes` actually
+/// found a drawable segment for). A subpath whose moveto is never followed by
+/// a draw contributes a color but no stroke, which would otherwise silently
+/// shift every later stroke onto the wrong pen via `group_by_pen`'s zip.
+fn assert_stroke_colors_aligned(stroke_colors: &[Color], strokes: &[Stroke]) {
+    assert_eq!(
+        stroke_colors.len(),
+        strokes.len(),
+        "stroke color count ({}) doesn't match stroke count ({}); a subpath with a moveto but no drawable segment desyncs pen assignment",
+        stroke_colors.len(),
+        strokes.len(),
+    );
+}
+
 struct Plotter {
     driver: isize,
     args: Args,
@@ -77,6 +438,12 @@ struct Plotter {
 
 impl Plotter {
     pub fn new(args: Args) -> Self {
+        // `--preview` never touches the hardware (it returns before the write
+        // loop in `plot`), so don't require an adapter to be attached for it.
+        if args.preview {
+            return Self { driver: 0, args };
+        }
+
         unsafe {
             let mut driver: isize = 0;
             let res = opencbm::cbm_driver_open_ex(
@@ -96,11 +463,103 @@ impl Plotter {
     }
 
     pub fn plot(mut self) {
+        let (raw_plan, stroke_colors) = if raster::is_raster_image(&self.args.file) {
+            self.build_raster_plan()
+        } else {
+            self.build_svg_plan()
+        };
+
+        let strokes = build_strokes(&raw_plan);
+        assert_stroke_colors_aligned(&stroke_colors, &strokes);
+
+        let pen_mappings = parse_pen_mappings(&self.args.pen);
+        let pens: Vec<u8> = stroke_colors.iter().map(|&color| nearest_pen(color, &pen_mappings)).collect();
+        let groups = group_by_pen(strokes, pens);
+
+        let mut plan = Vec::new();
+        // Tracks where the pen actually is across groups, so switching pens
+        // doesn't reset each group's tour to the machine origin and defeat
+        // the point of optimizing.
+        let mut pos: Point = (0, 0);
+        for (pen, strokes) in groups {
+            let strokes = if self.args.optimize {
+                optimize_strokes(strokes, pos)
+            } else {
+                strokes
+            };
+
+            if let Some(last) = strokes.last() {
+                pos = last.end();
+            }
+
+            plan.push(PlotCommand::SelectPen(pen));
+            plan.extend(strokes_to_plan(&strokes));
+        }
+
+        if let Some(path) = &self.args.simulate {
+            println!("Writing simulation SVG to {}", path.display());
+            simulate::write_simulation(&plan, path, self.args.width, self.args.height)
+                .expect("failed to write simulation SVG");
+        }
+
+        if self.args.preview {
+            println!("Rendering toolpath preview");
+            let grid = rasterize_plan(&plan, self.args.width, self.args.height);
+            print_sixel_preview(&grid);
+            return;
+        }
+
+        for command in plan {
+            match command {
+                PlotCommand::Move(x, y) => unsafe {
+                    let command = std::ffi::CString::new(format!("M,{},{}\n", x, y))
+                        .unwrap()
+                        .into_bytes_with_nul();
+
+                    self.write(PLOTTER_DEVICE, PLOTTER_SA_XY, command.as_slice());
+                },
+                PlotCommand::Draw(x, y) => unsafe {
+                    let command = std::ffi::CString::new(format!("D,{},{}", x, y))
+                        .unwrap()
+                        .into_bytes_with_nul();
+
+                    self.write(PLOTTER_DEVICE, PLOTTER_SA_XY, command.as_slice());
+                },
+                PlotCommand::SelectPen(pen) => unsafe {
+                    let command = std::ffi::CString::new(format!("P,{}\n", pen))
+                        .unwrap()
+                        .into_bytes_with_nul();
+
+                    self.write(PLOTTER_DEVICE, PLOTTER_SA_SELECT, command.as_slice());
+                },
+            }
+        }
+    }
+
+    /// Parses the input SVG and converts it to a flat `Move`/`Draw` plan via
+    /// `svg2gcode`, alongside each resulting stroke's resolved color.
+    ///
+    /// `svg2program` emits strokes in document order with one contiguous
+    /// `Move`+`Draw` run per *subpath*, and a `<path>`'s `d` attribute can
+    /// contain several subpaths (each new `M`/`m` lifts the pen), so a
+    /// path's color is repeated once per subpath rather than zipped 1:1 with
+    /// `<path>` elements.
+    fn build_svg_plan(&self) -> (Vec<PlotCommand>, Vec<Color>) {
         let contents = read_to_string(self.args.file.clone()).expect("failed to read file");
         let doc = Document::parse(contents.as_str()).expect("failed to parse file");
 
-        let tool_on = Some("Z0").map(snippet_parser).transpose().unwrap(); 
-        let tool_off = Some("Z1").map(snippet_parser).transpose().unwrap(); 
+        let stroke_colors: Vec<Color> = doc
+            .descendants()
+            .filter(|node| node.has_tag_name("path"))
+            .flat_map(|node| {
+                let color = node.attribute("stroke").and_then(parse_color).unwrap_or([0, 0, 0]);
+                let subpaths = node.attribute("d").map_or(1, count_subpaths);
+                std::iter::repeat_n(color, subpaths)
+            })
+            .collect();
+
+        let tool_on = Some("Z0").map(snippet_parser).transpose().unwrap();
+        let tool_off = Some("Z1").map(snippet_parser).transpose().unwrap();
 
         let machine = Machine::new(
             SupportedFunctionality { circular_interpolation: false},
@@ -112,7 +571,7 @@ impl Plotter {
 
         println!("Machine started, converting SVG");
         let gcode = svg2program(
-            &doc, 
+            &doc,
             &ConversionConfig {
                 tolerance: 0.1,
                 feedrate: 55.0,
@@ -121,7 +580,7 @@ impl Plotter {
             },
             ConversionOptions {
                 dimensions: [
-                    Some(Length::new(self.args.width as f64, LengthUnit::Mm)), 
+                    Some(Length::new(self.args.width as f64, LengthUnit::Mm)),
                     Some(Length::new(self.args.height as f64, LengthUnit::Mm))
                 ],
             },
@@ -129,6 +588,7 @@ impl Plotter {
         );
 
         let mut command = Commands::new_move();
+        let mut plan: Vec<PlotCommand> = Vec::new();
 
         for token in gcode {
             if let Token::Field(value) = token {
@@ -159,30 +619,80 @@ impl Plotter {
 
             if command.is_ready() {
                 match command {
-                    Commands::Reset => unsafe {
-                        opencbm::cbm_listen(self.driver, PLOTTER_DEVICE, PLOTTER_SA_RESET);
-                        opencbm::cbm_raw_write(self.driver, std::ptr::null(), 0);
-                        opencbm::cbm_unlisten(self.driver);
-                    },
-                    Commands::Move(ref opts) => unsafe {
-                        let command = std::ffi::CString::new(
-                            format!("M,{},{}\n", opts.x.unwrap(), opts.y.unwrap())
-                        ).unwrap()
-                         .into_bytes_with_nul();
-
-                        self.write(PLOTTER_DEVICE, PLOTTER_SA_XY, command.as_slice());
+                    Commands::Reset => {},
+                    Commands::Move(ref opts) => {
+                        plan.push(PlotCommand::Move(opts.x.unwrap(), opts.y.unwrap()));
                     },
-                    Commands::Draw(ref opts) => unsafe {
-                        let command = std::ffi::CString::new(
-                            format!("D,{},{}", opts.x.unwrap(), opts.y.unwrap())
-                        ).unwrap()
-                         .into_bytes_with_nul();
-                        
-                        self.write(PLOTTER_DEVICE, PLOTTER_SA_XY, command.as_slice());
+                    Commands::Draw(ref opts) => {
+                        plan.push(PlotCommand::Draw(opts.x.unwrap(), opts.y.unwrap()));
                     }
                 }
             }
         }
+
+        (plan, stroke_colors)
+    }
+
+    /// Traces a raster image's foreground contours and converts the resulting
+    /// polylines into a flat `Move`/`Draw` plan, scaled to fit the plotter bounds.
+    ///
+    /// Raster tracing carries no color information, so every stroke is reported
+    /// as black and goes to whichever pen black quantizes to (pen 0 when no
+    /// `--pen` mappings are given).
+    fn build_raster_plan(&self) -> (Vec<PlotCommand>, Vec<Color>) {
+        println!("Raster image detected, tracing contours");
+
+        let config = ConversionConfig {
+            tolerance: 0.1,
+            feedrate: 55.0,
+            dpi: 200.0,
+            origin: [None, None],
+        };
+        let polylines = raster::trace_contours(&self.args.file, config.tolerance);
+
+        let (min_x, max_x, min_y, max_y) = polylines.iter().flatten().fold(
+            (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+            |(min_x, max_x, min_y, max_y), &(x, y)| {
+                (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+            },
+        );
+
+        let span_x = (max_x - min_x).max(1.0);
+        let span_y = (max_y - min_y).max(1.0);
+        let scale = (self.args.width as f64 / span_x).min(self.args.height as f64 / span_y);
+        let height_bound = span_y * scale;
+
+        // Image rows increase downward, but the plotter treats its origin as
+        // bottom-left with Y increasing upward (same convention the SVG path
+        // and the sixel preview already use), so flip Y here.
+        let to_device = |x: f64, y: f64| -> (u32, u32) {
+            let px = (x - min_x) * scale;
+            let py = height_bound - (y - min_y) * scale;
+            (px as u32, py.max(0.0) as u32)
+        };
+
+        let mut plan = Vec::new();
+        let mut stroke_colors = Vec::new();
+
+        for polyline in polylines {
+            if polyline.len() < 2 {
+                continue;
+            }
+
+            let mut points = polyline.into_iter();
+            let (x, y) = points.next().unwrap();
+            let (x, y) = to_device(x, y);
+            plan.push(PlotCommand::Move(x, y));
+
+            for (x, y) in points {
+                let (x, y) = to_device(x, y);
+                plan.push(PlotCommand::Draw(x, y));
+            }
+
+            stroke_colors.push([0, 0, 0]);
+        }
+
+        (plan, stroke_colors)
     }
 
     unsafe fn write(&mut self, addr: u8, sec_addr: u8, data: &[u8]) {
@@ -201,6 +711,10 @@ impl Plotter {
 
 impl Drop for Plotter {
     fn drop(&mut self) {
+        if self.args.preview {
+            return;
+        }
+
         unsafe {
             opencbm::cbm_driver_close(self.driver);
         }
@@ -210,7 +724,7 @@ impl Drop for Plotter {
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(long, help = "Path to the SVG file to plot")]
+    #[arg(long, help = "Path to the SVG or raster (e.g. PNG) file to plot")]
     pub file: PathBuf,
     
     #[arg(long, help = "OpenCBM adapter name")]
@@ -221,6 +735,18 @@ struct Args {
 
     #[arg(long, help = "Width in mm (Max 447")]
     pub width: u32,
+
+    #[arg(long, help = "Reorder strokes to minimize pen-up travel before plotting")]
+    pub optimize: bool,
+
+    #[arg(long, help = "Render the planned toolpath as a sixel preview and skip the hardware")]
+    pub preview: bool,
+
+    #[arg(long, help = "Map a stroke color to a pen index, e.g. --pen \"#FF0000=1\" (repeatable)")]
+    pub pen: Vec<String>,
+
+    #[arg(long, help = "Write an SVG simulation of the planned toolpath, alongside or instead of plotting")]
+    pub simulate: Option<PathBuf>,
 }
 
 fn main() {
@@ -233,3 +759,131 @@ fn main() {
     let plotter = Plotter::new(args);
     plotter.plot();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tour_cost(strokes: &[Stroke]) -> f64 {
+        let mut pos: Point = (0, 0);
+        let mut cost = 0.0;
+        for stroke in strokes {
+            cost += pen_up_distance(pos, stroke.start());
+            pos = stroke.end();
+        }
+        cost
+    }
+
+    #[test]
+    fn two_opt_refine_never_makes_the_tour_worse() {
+        let strokes = vec![
+            Stroke { points: vec![(10, 0), (11, 0)] },
+            Stroke { points: vec![(1, 0), (20, 0)] },
+        ];
+        let before = tour_cost(&strokes);
+
+        let mut refined = strokes;
+        two_opt_refine(&mut refined, (0, 0));
+
+        assert!(tour_cost(&refined) <= before);
+    }
+
+    #[test]
+    fn two_opt_refine_terminates() {
+        // Regression test for a swapped-cost formula that compared against
+        // edges that could never occur post-swap, causing `improved` to
+        // oscillate forever instead of converging.
+        let mut strokes = vec![
+            Stroke { points: vec![(10, 0), (11, 0)] },
+            Stroke { points: vec![(1, 0), (20, 0)] },
+        ];
+        two_opt_refine(&mut strokes, (0, 0));
+    }
+
+    #[test]
+    fn nearest_neighbor_order_picks_closest_stroke_first() {
+        let strokes = vec![
+            Stroke { points: vec![(100, 100), (110, 100)] },
+            Stroke { points: vec![(1, 1), (5, 5)] },
+        ];
+
+        let ordered = nearest_neighbor_order(strokes, (0, 0));
+
+        assert_eq!(ordered[0].start(), (1, 1));
+    }
+
+    #[test]
+    fn nearest_neighbor_order_reverses_strokes_entered_from_their_far_end() {
+        let strokes = vec![Stroke { points: vec![(5, 0), (0, 0)] }];
+
+        let ordered = nearest_neighbor_order(strokes, (0, 0));
+
+        assert_eq!(ordered[0].start(), (0, 0));
+        assert_eq!(ordered[0].end(), (5, 0));
+    }
+
+    #[test]
+    fn nearest_neighbor_order_starts_from_the_given_position_not_the_origin() {
+        // Regression test for per-pen optimization always assuming the pen
+        // starts each group's tour at the machine origin: here the origin is
+        // actually closer to the first stroke, but the pen is really at
+        // (100, 100), which is closest to the second.
+        let strokes = vec![
+            Stroke { points: vec![(1, 1), (2, 2)] },
+            Stroke { points: vec![(99, 99), (98, 98)] },
+        ];
+
+        let ordered = nearest_neighbor_order(strokes, (100, 100));
+
+        assert_eq!(ordered[0].start(), (99, 99));
+    }
+
+    #[test]
+    fn bresenham_covers_a_straight_horizontal_line() {
+        let mut points = Vec::new();
+        bresenham(0, 0, 4, 0, &mut |x, y| points.push((x, y)));
+
+        assert_eq!(points, vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn nearest_pen_defaults_to_zero_without_mappings() {
+        assert_eq!(nearest_pen([10, 20, 30], &[]), 0);
+    }
+
+    #[test]
+    fn nearest_pen_picks_the_closest_mapped_color() {
+        let mappings = vec![([255, 0, 0], 1), ([0, 255, 0], 2)];
+
+        assert_eq!(nearest_pen([250, 10, 10], &mappings), 1);
+        assert_eq!(nearest_pen([10, 240, 10], &mappings), 2);
+    }
+
+    #[test]
+    fn parse_color_handles_short_and_long_hex() {
+        assert_eq!(parse_color("#fff"), Some([255, 255, 255]));
+        assert_eq!(parse_color("#ff0000"), Some([255, 0, 0]));
+        assert_eq!(parse_color("none"), None);
+    }
+
+    #[test]
+    fn count_subpaths_counts_every_moveto() {
+        // A letter like "O" or "A": an outer contour plus an inner hole, each
+        // starting with its own moveto.
+        assert_eq!(count_subpaths("M0 0 L10 0 L10 10 Z M2 2 L8 2 L8 8 Z"), 2);
+        assert_eq!(count_subpaths("M0 0 L10 0 Z"), 1);
+        assert_eq!(count_subpaths("m0 0 l10 0 m5 5 l1 1 m1 1 l1 1"), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "desyncs pen assignment")]
+    fn assert_stroke_colors_aligned_panics_on_a_dangling_moveto() {
+        // e.g. "M0 0 L1 0 M5 5": count_subpaths counts 2 subpaths, but the
+        // second moveto has no segment after it, so build_strokes only ever
+        // produces one real Stroke for it.
+        let stroke_colors = vec![[0, 0, 0], [0, 0, 0]];
+        let strokes = vec![Stroke { points: vec![(0, 0), (1, 0)] }];
+
+        assert_stroke_colors_aligned(&stroke_colors, &strokes);
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::Path;
+
+/// Luma threshold below which a pixel is considered foreground (to be traced).
+const THRESHOLD: u8 = 128;
+
+/// 8-connected neighbor offsets in clockwise order, starting east.
+const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0), (1, 1), (0, 1), (-1, 1),
+    (-1, 0), (-1, -1), (0, -1), (1, -1),
+];
+
+/// A traced, simplified polyline in image pixel space (x, y).
+pub type Polyline = Vec<(f64, f64)>;
+
+/// Returns true if `path` looks like a raster image, by extension or PNG magic bytes.
+pub fn is_raster_image(path: &Path) -> bool {
+    let known_extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg" | "bmp" | "gif"));
+
+    if known_extension {
+        return true;
+    }
+
+    std::fs::read(path)
+        .map(|bytes| bytes.starts_with(&[0x89, 0x50, 0x4E, 0x47]))
+        .unwrap_or(false)
+}
+
+/// Loads a raster image, thresholds it to a binary mask, traces the boundary of
+/// each connected foreground region, and simplifies each boundary with
+/// Ramer-Douglas-Peucker at `tolerance`.
+pub fn trace_contours(path: &Path, tolerance: f64) -> Vec<Polyline> {
+    let image = image::open(path).expect("failed to decode raster image").to_luma8();
+    let (width, height) = image.dimensions();
+
+    let mut mask = vec![vec![false; width as usize]; height as usize];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        mask[y as usize][x as usize] = pixel.0[0] < THRESHOLD;
+    }
+
+    trace_boundaries(&mask)
+        .into_iter()
+        .map(|polyline| simplify(&polyline, tolerance))
+        .collect()
+}
+
+fn trace_boundaries(mask: &[Vec<bool>]) -> Vec<Polyline> {
+    let height = mask.len();
+    let width = mask.first().map_or(0, Vec::len);
+    let mut visited = vec![vec![false; width]; height];
+    let mut polylines = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            if mask[y][x] && !visited[y][x] && is_boundary_pixel(mask, x, y) {
+                let polyline = moore_trace(mask, x, y);
+                for &(px, py) in &polyline {
+                    visited[py as usize][px as usize] = true;
+                }
+                polylines.push(polyline);
+            }
+        }
+    }
+
+    polylines
+}
+
+fn is_boundary_pixel(mask: &[Vec<bool>], x: usize, y: usize) -> bool {
+    let height = mask.len() as i32;
+    let width = mask.first().map_or(0, Vec::len) as i32;
+
+    DIRECTIONS.iter().any(|&(dx, dy)| {
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        nx < 0 || ny < 0 || nx >= width || ny >= height || !mask[ny as usize][nx as usize]
+    })
+}
+
+/// Moore-neighbor boundary tracing, starting at a known foreground boundary pixel.
+fn moore_trace(mask: &[Vec<bool>], start_x: usize, start_y: usize) -> Polyline {
+    let height = mask.len() as i32;
+    let width = mask.first().map_or(0, Vec::len) as i32;
+    let is_set = |x: i32, y: i32| x >= 0 && y >= 0 && x < width && y < height && mask[y as usize][x as usize];
+
+    let start = (start_x as i32, start_y as i32);
+    let (mut cx, mut cy) = start;
+    let mut entry_dir = 4;
+    let mut polyline = vec![(cx as f64, cy as f64)];
+
+    // Jacob's stopping criterion: a boundary can pass back through the start
+    // pixel's coordinates well before the contour has actually closed (e.g.
+    // the apex of a one-pixel-wide "Λ", which joins two branches), so matching on coordinates
+    // alone stops too early. Only stop once we're back at the start pixel and
+    // about to repeat the very first step taken away from it.
+    let mut first_step_dir = None;
+
+    loop {
+        let next = (1..=8)
+            .map(|step| (entry_dir + step) % 8)
+            .find(|&dir| {
+                let (dx, dy) = DIRECTIONS[dir];
+                is_set(cx + dx, cy + dy)
+            });
+
+        let Some(dir) = next else {
+            break;
+        };
+
+        if (cx, cy) == start && first_step_dir == Some(dir) {
+            break;
+        }
+
+        let (dx, dy) = DIRECTIONS[dir];
+        cx += dx;
+        cy += dy;
+        entry_dir = (dir + 4) % 8;
+        first_step_dir.get_or_insert(dir);
+
+        polyline.push((cx as f64, cy as f64));
+    }
+
+    polyline
+}
+
+/// Ramer-Douglas-Peucker polyline simplification.
+fn simplify(points: &[(f64, f64)], tolerance: f64) -> Polyline {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    simplify_range(points, 0, points.len() - 1, tolerance, &mut keep);
+
+    points.iter().zip(keep).filter_map(|(&point, kept)| kept.then_some(point)).collect()
+}
+
+fn simplify_range(points: &[(f64, f64)], start: usize, end: usize, tolerance: f64, keep: &mut [bool]) {
+    if end <= start + 1 {
+        return;
+    }
+
+    let (mut farthest_index, mut farthest_dist) = (start, 0.0);
+    for (i, &point) in points.iter().enumerate().take(end).skip(start + 1) {
+        let dist = perpendicular_distance(point, points[start], points[end]);
+        if dist > farthest_dist {
+            farthest_index = i;
+            farthest_dist = dist;
+        }
+    }
+
+    if farthest_dist > tolerance {
+        keep[farthest_index] = true;
+        simplify_range(points, start, farthest_index, tolerance, keep);
+        simplify_range(points, farthest_index, end, tolerance, keep);
+    }
+}
+
+fn perpendicular_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let len = (dx * dx + dy * dy).sqrt();
+
+    if len == 0.0 {
+        return ((point.0 - start.0).powi(2) + (point.1 - start.1).powi(2)).sqrt();
+    }
+
+    ((point.0 - start.0) * dy - (point.1 - start.1) * dx).abs() / len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_raster_image_detects_known_extensions() {
+        assert!(is_raster_image(Path::new("art.png")));
+        assert!(is_raster_image(Path::new("ART.PNG")));
+        assert!(!is_raster_image(Path::new("art.svg")));
+    }
+
+    #[test]
+    fn moore_trace_walks_the_full_boundary_of_a_filled_square() {
+        let mask = vec![
+            vec![true, true, true],
+            vec![true, true, true],
+            vec![true, true, true],
+        ];
+
+        let polyline = moore_trace(&mask, 0, 0);
+
+        // Every border pixel of the 3x3 square is a boundary pixel; the interior
+        // (1, 1) is not.
+        assert!(!polyline.contains(&(1.0, 1.0)));
+        assert!(polyline.len() >= 8);
+    }
+
+    #[test]
+    fn moore_trace_closes_the_loop_at_a_concave_junction_instead_of_stopping_early() {
+        // A thin "+": starting the trace at the center pixel steps north to
+        // (2, 1), diagonally to (1, 2) and back to the center, where the next
+        // step would repeat its first departure direction (north), so the
+        // trace stops there with the return to the center included, giving
+        // a closed 4-point path.
+        let mask = vec![
+            vec![false, false, true, false, false],
+            vec![false, false, true, false, false],
+            vec![true, true, true, true, true],
+            vec![false, false, true, false, false],
+            vec![false, false, true, false, false],
+        ];
+
+        let polyline = moore_trace(&mask, 2, 2);
+
+        assert_eq!(polyline.first(), polyline.last());
+        assert_eq!(polyline.len(), 4);
+    }
+
+    #[test]
+    fn simplify_collapses_colinear_points() {
+        let points = vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 2.0)];
+
+        let simplified = simplify(&points, 0.5);
+
+        assert_eq!(simplified, vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0)]);
+    }
+
+    #[test]
+    fn simplify_keeps_short_polylines_untouched() {
+        let points = vec![(0.0, 0.0), (1.0, 1.0)];
+
+        assert_eq!(simplify(&points, 0.5), points);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::PlotCommand;
+
+/// Writes an SVG depicting exactly what the plotter will do: solid strokes for
+/// pen-down `Draw` segments, dashed light-gray lines for pen-up `Move`
+/// repositioning, in the real plotter coordinate space with `(width, height)`
+/// as the viewBox.
+///
+/// Formats shapes directly to the writer as it goes (à la `svg_fmt`) rather
+/// than building a DOM, since the output is just a flat list of lines.
+pub fn write_simulation(plan: &[PlotCommand], path: &Path, width: u32, height: u32) -> io::Result<()> {
+    let mut file = File::create(path)?;
+
+    writeln!(
+        file,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" width="{width}" height="{height}">"#
+    )?;
+
+    let mut pos: Option<(u32, u32)> = None;
+
+    for command in plan {
+        match *command {
+            PlotCommand::Move(x, y) => {
+                if let Some((px, py)) = pos {
+                    write_pen_up(&mut file, px, py, x, y)?;
+                }
+                pos = Some((x, y));
+            }
+            PlotCommand::Draw(x, y) => {
+                if let Some((px, py)) = pos {
+                    write_pen_down(&mut file, px, py, x, y)?;
+                }
+                pos = Some((x, y));
+            }
+            PlotCommand::SelectPen(_) => {}
+        }
+    }
+
+    writeln!(file, "</svg>")
+}
+
+fn write_pen_down(file: &mut File, x1: u32, y1: u32, x2: u32, y2: u32) -> io::Result<()> {
+    writeln!(
+        file,
+        r#"  <line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="black" stroke-width="1" />"#
+    )
+}
+
+fn write_pen_up(file: &mut File, x1: u32, y1: u32, x2: u32, y2: u32) -> io::Result<()> {
+    writeln!(
+        file,
+        r#"  <line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="lightgray" stroke-width="0.5" stroke-dasharray="4,2" />"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_solid_draw_lines_and_dashed_move_lines() {
+        let plan = vec![
+            PlotCommand::Move(0, 0),
+            PlotCommand::Draw(10, 0),
+            PlotCommand::SelectPen(1),
+            PlotCommand::Move(20, 20),
+            PlotCommand::Draw(30, 20),
+        ];
+
+        let path = std::env::temp_dir().join("cbm1520_simulate_test.svg");
+        write_simulation(&plan, &path, 100, 100).expect("write_simulation should succeed");
+        let contents = std::fs::read_to_string(&path).expect("simulation file should exist");
+        std::fs::remove_file(&path).ok();
+
+        assert!(contents.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100""#));
+        assert!(contents.contains(r#"<line x1="0" y1="0" x2="10" y2="0" stroke="black""#));
+        assert!(contents.contains(r#"stroke="lightgray""#));
+        assert!(contents.contains("stroke-dasharray=\"4,2\""));
+        assert!(contents.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn select_pen_does_not_draw_a_line() {
+        let plan = vec![PlotCommand::SelectPen(3)];
+
+        let path = std::env::temp_dir().join("cbm1520_simulate_test_empty.svg");
+        write_simulation(&plan, &path, 50, 50).expect("write_simulation should succeed");
+        let contents = std::fs::read_to_string(&path).expect("simulation file should exist");
+        std::fs::remove_file(&path).ok();
+
+        assert!(!contents.contains("<line"));
+    }
+}